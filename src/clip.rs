@@ -0,0 +1,212 @@
+//! Geographic bounding-box filter that extracts a sub-feed.
+//!
+//! Carves a city-sized slice out of a country-wide GTFS feed: keeps only
+//! `stops.txt` rows and `shapes.txt` points that fall inside a bounding box,
+//! writing the reduced feed to a separate output directory rather than
+//! overwriting the original. Every other `.txt` file in the feed (agency,
+//! routes, trips, calendar, stop_times, etc.) is copied through unfiltered
+//! so the result is still a complete, loadable GTFS feed.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord, Writer};
+
+/// A `min_lon,min_lat,max_lon,max_lat` geographic bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    fn contains(&self, lat: f64, lon: f64) -> bool {
+        lat >= self.min_lat && lat <= self.max_lat && lon >= self.min_lon && lon <= self.max_lon
+    }
+}
+
+/// Parses a `--bbox min_lon,min_lat,max_lon,max_lat` argument.
+pub fn parse_bbox(s: &str) -> Result<BoundingBox, String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(format!(
+            "invalid --bbox '{}': expected min_lon,min_lat,max_lon,max_lat",
+            s
+        ));
+    }
+    let mut nums = [0.0; 4];
+    for (i, part) in parts.iter().enumerate() {
+        nums[i] = part
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("invalid --bbox '{}': {}", s, e))?;
+    }
+    Ok(BoundingBox {
+        min_lon: nums[0],
+        min_lat: nums[1],
+        max_lon: nums[2],
+        max_lat: nums[3],
+    })
+}
+
+/// How `shapes.txt` points outside the bounding box are treated.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ShapeClipMode {
+    /// Keep only the individual points that fall inside the box, trimming shapes.
+    PointsOnly,
+    /// Keep every point of a shape if any one of its points falls inside the box.
+    WholeShapeIfAnyPointInside,
+}
+
+/// Counts of what was kept from each clipped file.
+pub struct ClipSummary {
+    pub stops_kept: usize,
+    pub stops_total: usize,
+    pub shape_points_kept: usize,
+    pub shape_points_total: usize,
+    /// Number of other GTFS files (agency.txt, routes.txt, trips.txt, ...)
+    /// copied through to `output_dir` unfiltered.
+    pub passthrough_files: usize,
+}
+
+fn required_index(headers: &StringRecord, name: &str) -> Result<usize, Box<dyn Error>> {
+    headers
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("missing required column '{}'", name).into())
+}
+
+fn parse_coord(record: &StringRecord, idx: usize) -> f64 {
+    record
+        .get(idx)
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(f64::NAN)
+}
+
+/// Filters `stops.txt` and `shapes.txt` in `gtfs_dir` down to `bbox`, writing
+/// the reduced files into `output_dir`. Column order is discovered
+/// dynamically, same as the main fixer, so the filter works regardless of
+/// how the feed's columns are ordered.
+pub fn clip_feed(
+    gtfs_dir: &Path,
+    output_dir: &Path,
+    bbox: BoundingBox,
+    shape_mode: ShapeClipMode,
+) -> Result<ClipSummary, Box<dyn Error>> {
+    fs::create_dir_all(output_dir)?;
+
+    let mut stops_total = 0;
+    let mut stops_kept = 0;
+    let stops_path = gtfs_dir.join("stops.txt");
+    if stops_path.exists() {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(&stops_path)?;
+        let headers = reader.headers()?.clone();
+        let lat_idx = required_index(&headers, "stop_lat")?;
+        let lon_idx = required_index(&headers, "stop_lon")?;
+
+        let out_path = output_dir.join("stops.txt");
+        let mut writer = Writer::from_writer(BufWriter::new(File::create(&out_path)?));
+        writer.write_record(&headers)?;
+        for result in reader.records() {
+            let record = result?;
+            stops_total += 1;
+            let lat = parse_coord(&record, lat_idx);
+            let lon = parse_coord(&record, lon_idx);
+            if bbox.contains(lat, lon) {
+                writer.write_record(&record)?;
+                stops_kept += 1;
+            }
+        }
+        writer.flush()?;
+    }
+
+    let mut shape_points_total = 0;
+    let mut shape_points_kept = 0;
+    let shapes_path = gtfs_dir.join("shapes.txt");
+    if shapes_path.exists() {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(&shapes_path)?;
+        let headers = reader.headers()?.clone();
+        let shape_id_idx = required_index(&headers, "shape_id")?;
+        let lat_idx = required_index(&headers, "shape_pt_lat")?;
+        let lon_idx = required_index(&headers, "shape_pt_lon")?;
+
+        let records: Vec<StringRecord> = reader.records().collect::<Result<_, _>>()?;
+        shape_points_total = records.len();
+
+        // In "whole shape" mode, a shape qualifies for inclusion as soon as
+        // any one of its points falls inside the box.
+        let qualifying_shapes: Option<HashSet<String>> = match shape_mode {
+            ShapeClipMode::WholeShapeIfAnyPointInside => {
+                let mut qualifying = HashSet::new();
+                for record in &records {
+                    let lat = parse_coord(record, lat_idx);
+                    let lon = parse_coord(record, lon_idx);
+                    if bbox.contains(lat, lon) {
+                        qualifying.insert(record.get(shape_id_idx).unwrap_or("").to_string());
+                    }
+                }
+                Some(qualifying)
+            }
+            ShapeClipMode::PointsOnly => None,
+        };
+
+        let out_path = output_dir.join("shapes.txt");
+        let mut writer = Writer::from_writer(BufWriter::new(File::create(&out_path)?));
+        writer.write_record(&headers)?;
+        for record in &records {
+            let keep = match &qualifying_shapes {
+                Some(qualifying) => qualifying.contains(record.get(shape_id_idx).unwrap_or("")),
+                None => {
+                    let lat = parse_coord(record, lat_idx);
+                    let lon = parse_coord(record, lon_idx);
+                    bbox.contains(lat, lon)
+                }
+            };
+            if keep {
+                writer.write_record(record)?;
+                shape_points_kept += 1;
+            }
+        }
+        writer.flush()?;
+    }
+
+    // --- Pass through every other GTFS file unfiltered ---
+    // stops.txt and shapes.txt were spatially filtered above; the rest of
+    // the feed (agency.txt, routes.txt, trips.txt, calendar*.txt,
+    // stop_times.txt, feed_info.txt, ...) still applies to the clipped
+    // feed as-is, so copy it across verbatim. Without this the output
+    // directory isn't a loadable GTFS feed at all.
+    let mut passthrough_files = 0;
+    for entry in fs::read_dir(gtfs_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name == "stops.txt" || name == "shapes.txt" {
+            continue;
+        }
+        fs::copy(&path, output_dir.join(name))?;
+        passthrough_files += 1;
+    }
+
+    Ok(ClipSummary {
+        stops_kept,
+        stops_total,
+        shape_points_kept,
+        shape_points_total,
+        passthrough_files,
+    })
+}
@@ -0,0 +1,192 @@
+//! Spatial de-duplication of `stops.txt` via coordinate grid hashing.
+//!
+//! Feeds frequently contain near-duplicate stops sitting at essentially the
+//! same location (e.g. imported from multiple sources). This module buckets
+//! stops into a hash grid keyed by rounded lat/lon, confirms candidates
+//! within a bucket are actually close together via the haversine distance,
+//! and optionally merges them in place.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord, Writer};
+
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// Options controlling how aggressively `stops.txt` is de-duplicated.
+pub struct DedupeOptions {
+    /// Number of decimal places stop coordinates are rounded to before
+    /// bucketing (e.g. 5 decimal places is roughly 1.1m of precision).
+    pub precision: u32,
+    /// Maximum haversine distance, in meters, for two stops in the same
+    /// bucket to be considered the same physical stop.
+    pub threshold_meters: f64,
+    /// If true, rewrite `stops.txt` keeping only the canonical stop from
+    /// each duplicate group and emit a `stop_id,merged_into` mapping table.
+    /// If false, only report what would be merged.
+    pub merge: bool,
+}
+
+/// Outcome of a dedupe pass.
+pub enum DedupeOutcome {
+    /// `stops.txt` wasn't present, so there was nothing to dedupe.
+    NotPresent,
+    /// The pass ran; how many duplicate groups were found (possibly zero)
+    /// and how many stops were (or, without `merge`, would be) merged.
+    Report {
+        duplicate_groups: usize,
+        merged_stops: usize,
+    },
+}
+
+/// Great-circle distance between two lat/lon points, in meters.
+fn haversine_distance_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1_rad, lat2_rad) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1_rad.cos() * lat2_rad.cos() * (d_lon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().asin();
+    EARTH_RADIUS_METERS * c
+}
+
+/// Rounds a lat/lon pair to `precision` decimal places and returns the
+/// resulting grid cell key.
+fn grid_key(lat: f64, lon: f64, precision: u32) -> (i64, i64) {
+    let factor = 10f64.powi(precision as i32);
+    ((lat * factor).round() as i64, (lon * factor).round() as i64)
+}
+
+/// Buckets the stops in `stops_path` into a coordinate hash grid, confirms
+/// same-bucket candidates are within `threshold_meters` of each other, and
+/// (with `merge: true`) rewrites the file keeping one canonical `stop_id`
+/// per group plus a `stop_id,merged_into` mapping table.
+///
+/// Stops with different `location_type` values are never merged, and the
+/// first-seen `stop_id` in each group is always kept as canonical so the
+/// result is deterministic.
+pub fn dedupe_stops(stops_path: &Path, opts: &DedupeOptions) -> Result<DedupeOutcome, Box<dyn Error>> {
+    if !stops_path.exists() {
+        return Ok(DedupeOutcome::NotPresent);
+    }
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(stops_path)?;
+    let headers = reader.headers()?.clone();
+
+    let header_map: HashMap<String, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.trim().to_lowercase(), i))
+        .collect();
+    let stop_id_idx = *header_map
+        .get("stop_id")
+        .ok_or("stops.txt is missing required column 'stop_id'")?;
+    let lat_idx = *header_map
+        .get("stop_lat")
+        .ok_or("stops.txt is missing required column 'stop_lat'")?;
+    let lon_idx = *header_map
+        .get("stop_lon")
+        .ok_or("stops.txt is missing required column 'stop_lon'")?;
+    let location_type_idx = header_map.get("location_type").copied();
+
+    let mut records: Vec<StringRecord> = Vec::new();
+    let mut coords: Vec<(f64, f64)> = Vec::new();
+    for result in reader.records() {
+        let record = result?;
+        let lat: f64 = record
+            .get(lat_idx)
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(f64::NAN);
+        let lon: f64 = record
+            .get(lon_idx)
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(f64::NAN);
+        coords.push((lat, lon));
+        records.push(record);
+    }
+
+    // Bucket row indices into grid cells keyed by rounded lat/lon.
+    let mut buckets: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, &(lat, lon)) in coords.iter().enumerate() {
+        if lat.is_nan() || lon.is_nan() {
+            continue;
+        }
+        buckets
+            .entry(grid_key(lat, lon, opts.precision))
+            .or_default()
+            .push(i);
+    }
+
+    let mut duplicate_groups = 0;
+    let mut mapping: Vec<(String, String)> = Vec::new();
+    let mut merged_rows: HashMap<usize, usize> = HashMap::new(); // row idx -> canonical row idx
+
+    for rows in buckets.values().filter(|rows| rows.len() > 1) {
+        let mut rows = rows.clone();
+        rows.sort_unstable(); // preserve first-seen order for a deterministic canonical pick
+        let canonical = rows[0];
+        let canonical_location_type = location_type_idx.map(|idx| records[canonical].get(idx).unwrap_or(""));
+
+        let mut group_members = Vec::new();
+        for &row in &rows[1..] {
+            let location_type = location_type_idx.map(|idx| records[row].get(idx).unwrap_or(""));
+            if location_type != canonical_location_type {
+                continue; // never merge stops with different location_type
+            }
+            let (lat1, lon1) = coords[canonical];
+            let (lat2, lon2) = coords[row];
+            if haversine_distance_meters(lat1, lon1, lat2, lon2) <= opts.threshold_meters {
+                group_members.push(row);
+            }
+        }
+
+        if group_members.is_empty() {
+            continue;
+        }
+
+        duplicate_groups += 1;
+        let canonical_id = records[canonical].get(stop_id_idx).unwrap_or("").to_string();
+        for &row in &group_members {
+            merged_rows.insert(row, canonical);
+            let dup_id = records[row].get(stop_id_idx).unwrap_or("").to_string();
+            mapping.push((dup_id, canonical_id.clone()));
+        }
+    }
+
+    let merged_stops = mapping.len();
+
+    if opts.merge && merged_stops > 0 {
+        let temp_path = stops_path.with_extension("txt.tmp");
+        {
+            let out_file = File::create(&temp_path)?;
+            let mut writer = Writer::from_writer(BufWriter::new(out_file));
+            writer.write_record(&headers)?;
+            for (i, record) in records.iter().enumerate() {
+                if !merged_rows.contains_key(&i) {
+                    writer.write_record(record)?;
+                }
+            }
+            writer.flush()?;
+        }
+        fs::rename(&temp_path, stops_path)?;
+
+        let mapping_path = stops_path.with_file_name("stops_merged.csv");
+        let mapping_file = File::create(&mapping_path)?;
+        let mut mapping_writer = Writer::from_writer(BufWriter::new(mapping_file));
+        mapping_writer.write_record(["stop_id", "merged_into"])?;
+        for (dup_id, canonical_id) in &mapping {
+            mapping_writer.write_record([dup_id.as_str(), canonical_id.as_str()])?;
+        }
+        mapping_writer.flush()?;
+    }
+
+    Ok(DedupeOutcome::Report {
+        duplicate_groups,
+        merged_stops,
+    })
+}
@@ -0,0 +1,149 @@
+//! Output formats for fixed GTFS files.
+//!
+//! GTFS itself is CSV, but downstream JSON-oriented search/index pipelines
+//! often want one JSON object per record instead. This module lets the
+//! fixer's writer path emit NDJSON (one object per line) or a single JSON
+//! array in addition to the default CSV, using the same header map already
+//! built while fixing coordinates as the JSON key set.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use csv::{StringRecord, Writer as CsvWriter};
+use serde_json::{Map, Value};
+
+/// The format fixed files are written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    Ndjson,
+    Json,
+}
+
+impl OutputFormat {
+    /// File extension fixed files are written with in this format; CSV keeps
+    /// GTFS's native `.txt`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "txt",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+/// Computes the output filename for `filename` in `format`, swapping the
+/// extension for non-CSV formats (e.g. `stops.txt` -> `stops.ndjson`).
+pub fn output_filename(filename: &str, format: OutputFormat) -> String {
+    if format == OutputFormat::Csv {
+        return filename.to_string();
+    }
+    let stem = Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(filename);
+    format!("{}.{}", stem, format.extension())
+}
+
+/// Writes processed GTFS records out in whichever format was requested.
+/// `Csv` is boxed since `csv::Writer` is much larger than the other
+/// variants' payloads; without it this enum would needlessly inflate every
+/// `RecordWriter` to the size of its biggest variant.
+pub enum RecordWriter {
+    Csv(Box<CsvWriter<BufWriter<File>>>),
+    Ndjson(BufWriter<File>),
+    Json { writer: BufWriter<File>, wrote_first: bool },
+}
+
+impl RecordWriter {
+    pub fn create(path: &Path, format: OutputFormat) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(path)?;
+        Ok(match format {
+            OutputFormat::Csv => {
+                RecordWriter::Csv(Box::new(CsvWriter::from_writer(BufWriter::new(file))))
+            }
+            OutputFormat::Ndjson => RecordWriter::Ndjson(BufWriter::new(file)),
+            OutputFormat::Json => {
+                let mut writer = BufWriter::new(file);
+                writer.write_all(b"[")?;
+                RecordWriter::Json {
+                    writer,
+                    wrote_first: false,
+                }
+            }
+        })
+    }
+
+    /// Writes the header row; a no-op for the JSON formats, which carry
+    /// field names in each record instead of a separate header line.
+    pub fn write_header(&mut self, headers: &StringRecord) -> Result<(), Box<dyn Error>> {
+        if let RecordWriter::Csv(writer) = self {
+            writer.write_record(headers)?;
+        }
+        Ok(())
+    }
+
+    /// Writes one processed record. `fields` are the already-formatted
+    /// output field values; `fixed_indices` marks which of them are numeric
+    /// fields that should be emitted as JSON numbers rather than strings.
+    pub fn write_record(
+        &mut self,
+        headers: &StringRecord,
+        fields: &[String],
+        fixed_indices: &[usize],
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            RecordWriter::Csv(writer) => {
+                writer.write_record(fields)?;
+            }
+            RecordWriter::Ndjson(writer) => {
+                let value = record_to_json(headers, fields, fixed_indices);
+                serde_json::to_writer(&mut *writer, &value)?;
+                writer.write_all(b"\n")?;
+            }
+            RecordWriter::Json { writer, wrote_first } => {
+                if *wrote_first {
+                    writer.write_all(b",")?;
+                }
+                *wrote_first = true;
+                let value = record_to_json(headers, fields, fixed_indices);
+                serde_json::to_writer(&mut *writer, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes buffered output and, for the `json` format, closes the array.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            RecordWriter::Csv(mut writer) => writer.flush()?,
+            RecordWriter::Ndjson(mut writer) => writer.flush()?,
+            RecordWriter::Json { mut writer, .. } => {
+                writer.write_all(b"]")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn record_to_json(headers: &StringRecord, fields: &[String], fixed_indices: &[usize]) -> Value {
+    let mut map = Map::new();
+    for (i, field) in fields.iter().enumerate() {
+        let key = headers.get(i).unwrap_or("").to_string();
+        let value = if fixed_indices.contains(&i) {
+            field
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .unwrap_or_else(|| Value::String(field.clone()))
+        } else {
+            Value::String(field.clone())
+        };
+        map.insert(key, value);
+    }
+    Value::Object(map)
+}
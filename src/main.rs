@@ -1,25 +1,192 @@
 use std::collections::HashMap; // To store header indices
-use std::env;
 use std::error::Error;
-use std::fs::{remove_file, rename, File};
-use std::io::{stdout, BufReader, BufWriter, Write}; // Added Write for flushing stdout
+use std::fmt;
+use std::fs::{self, remove_file, rename, File};
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
-use csv::{ReaderBuilder, StringRecord, Writer};
+use std::thread;
+use clap::Parser;
+use csv::{ReaderBuilder, StringRecord};
 
-// --- Configuration ---
-// Names of the files within the GTFS directory
-const STOPS_FILENAME: &str = "stops.txt";
-const SHAPES_FILENAME: &str = "shapes.txt";
-// Temporary file suffix
+mod clip;
+mod dedupe;
+mod format;
+mod validation;
+
+// Temporary file suffix used while fixing a file in place
 const TEMP_SUFFIX: &str = ".tmp";
-// Target column names (case-insensitive comparison will be used)
-const STOP_LAT_COLUMN_NAME: &str = "stop_lat";
-const STOP_LON_COLUMN_NAME: &str = "stop_lon";
-const SHAPE_LAT_COLUMN_NAME: &str = "shape_pt_lat";
-const SHAPE_LON_COLUMN_NAME: &str = "shape_pt_lon";
-// Number of decimal places for output coordinates
-const COORDINATE_PRECISION: usize = 8;
-// --- End Configuration ---
+
+/// Reformats GTFS coordinate (and coordinate-adjacent) fields to a fixed
+/// number of decimal places.
+#[derive(Parser, Debug)]
+#[command(name = "gtfs-fixer", about, version)]
+struct Opt {
+    /// Path to the GTFS feed directory to fix
+    gtfs_dir: PathBuf,
+
+    /// Number of decimal places to format numeric fields to
+    #[arg(long, default_value_t = 8)]
+    precision: usize,
+
+    /// Write fixed files to this directory instead of overwriting the originals in place
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Report how many fields would change without writing any fixed files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Register an extra file to fix, as `name:col1,col2,...` (repeatable)
+    #[arg(long = "extra-file", value_parser = parse_extra_file)]
+    extra_file: Vec<FileSpec>,
+
+    /// After fixing stops.txt, look for near-duplicate stops via coordinate grid hashing
+    #[arg(long)]
+    dedupe_stops: bool,
+
+    /// Decimal places stop coordinates are rounded to when bucketing for dedupe (~1.1m at 5)
+    #[arg(long, default_value_t = 5)]
+    dedupe_precision: u32,
+
+    /// Maximum distance, in meters, for two stops in the same bucket to count as duplicates
+    #[arg(long, default_value_t = 1.1)]
+    dedupe_threshold_meters: f64,
+
+    /// Actually rewrite stops.txt to merge duplicates, instead of only reporting them
+    #[arg(long)]
+    merge: bool,
+
+    /// Extract a sub-feed: keep only stops/shape points inside this box, written to --output-dir
+    #[arg(long, value_parser = clip::parse_bbox)]
+    bbox: Option<clip::BoundingBox>,
+
+    /// How shapes.txt points outside --bbox are treated
+    #[arg(long, value_enum, default_value = "points-only")]
+    shape_clip_mode: clip::ShapeClipMode,
+
+    /// Output format for fixed files; ndjson/json require --output-dir
+    #[arg(long, value_enum, default_value = "csv")]
+    format: format::OutputFormat,
+
+    /// Dump every validation issue found while fixing to this CSV file
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+/// Describes one GTFS file we know how to fix: its filename and the numeric
+/// columns inside it that should be reformatted to fixed precision. Not every
+/// column needs to be present in every feed (e.g. `shape_dist_traveled` is
+/// optional on both `shapes.txt` and `stop_times.txt`), so columns are looked
+/// up against the header rather than assumed to exist.
+#[derive(Clone, Debug)]
+struct FileSpec {
+    filename: String,
+    numeric_columns: Vec<String>,
+    /// Whether finding none of `numeric_columns` in the header is an error.
+    /// `stop_times.txt`'s only numeric column, `shape_dist_traveled`, is
+    /// entirely optional in GTFS, so a feed lacking it is valid and should
+    /// be skipped rather than fail the whole run.
+    columns_required: bool,
+}
+
+/// The set of GTFS files this tool knows how to repair out of the box, and
+/// which of their columns carry coordinate (or coordinate-adjacent) values.
+/// `--extra-file` extends this list at runtime without a recompile.
+fn known_files() -> Vec<FileSpec> {
+    [
+        ("stops.txt", &["stop_lat", "stop_lon"][..], true),
+        (
+            "shapes.txt",
+            &["shape_pt_lat", "shape_pt_lon", "shape_dist_traveled"][..],
+            true,
+        ),
+        ("stop_times.txt", &["shape_dist_traveled"][..], false),
+    ]
+    .into_iter()
+    .map(|(filename, columns, columns_required)| FileSpec {
+        filename: filename.to_string(),
+        numeric_columns: columns.iter().map(|c| c.to_string()).collect(),
+        columns_required,
+    })
+    .collect()
+}
+
+/// Parses a `--extra-file name:lat_col,lon_col` argument into a `FileSpec`.
+/// Columns registered this way are required, since the caller is explicitly
+/// asking the tool to fix them.
+fn parse_extra_file(s: &str) -> Result<FileSpec, String> {
+    let (filename, columns) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid --extra-file '{}': expected name:col1,col2,...", s))?;
+    let numeric_columns: Vec<String> = columns
+        .split(',')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+    if numeric_columns.is_empty() {
+        return Err(format!("invalid --extra-file '{}': no columns given", s));
+    }
+    Ok(FileSpec {
+        filename: filename.trim().to_string(),
+        numeric_columns,
+        columns_required: true,
+    })
+}
+
+/// The outcome of processing a single GTFS file, used to build the final
+/// summary once every file has been handled.
+enum FileOutcome {
+    /// The file didn't exist in the feed, or it has none of its optional
+    /// numeric columns; not necessarily an error since several GTFS files
+    /// (and columns) are optional.
+    Skipped,
+    /// The file was processed (or, in dry-run mode, scanned) successfully.
+    Processed {
+        /// Number of rows that were written (or would have been, in dry-run mode).
+        processed_count: usize,
+        /// Number of individual field values that were changed (or would change).
+        changed_count: usize,
+        columns: Vec<String>,
+        dry_run: bool,
+    },
+    /// Something went wrong while processing the file.
+    Failed(String),
+}
+
+struct FileReport {
+    filename: String,
+    outcome: FileOutcome,
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            FileOutcome::Skipped => write!(
+                f,
+                "{}: skipped (not present, or no columns to fix)",
+                self.filename
+            ),
+            FileOutcome::Processed {
+                processed_count,
+                changed_count,
+                columns,
+                dry_run,
+            } => {
+                let verb = if *dry_run { "would fix" } else { "fixed" };
+                write!(
+                    f,
+                    "{}: {} {} field(s) across {} record(s) in column(s) [{}]",
+                    self.filename,
+                    verb,
+                    changed_count,
+                    processed_count,
+                    columns.join(", ")
+                )
+            }
+            FileOutcome::Failed(e) => write!(f, "{}: FAILED - {}", self.filename, e),
+        }
+    }
+}
 
 /// Attempts to parse a string potentially containing a floating-point number
 /// (including scientific notation) and formats it to a fixed number of decimal places.
@@ -27,87 +194,94 @@ const COORDINATE_PRECISION: usize = 8;
 ///
 /// # Arguments
 /// * `value_str` - The string slice to parse and format.
+/// * `precision` - The number of decimal places to format to.
 ///
 /// # Returns
 /// A `String` containing the formatted number or the original string on error.
-fn format_coordinate(value_str: &str) -> String {
+fn format_coordinate(value_str: &str, precision: usize) -> String {
     match value_str.trim().parse::<f64>() {
         // Successfully parsed as f64 (handles standard and scientific notation)
-        Ok(val) => format!("{:.prec$}", val, prec = COORDINATE_PRECISION),
+        Ok(val) => format!("{:.prec$}", val, prec = precision),
         // If parsing fails, return the original string unchanged
         Err(_) => value_str.to_string(),
     }
 }
 
-/// Finds the indices of specified columns in a CSV header record.
-/// Performs case-insensitive comparison.
+/// Finds the indices of whichever `col_names` are present in the CSV header,
+/// performing a case-insensitive comparison. Unlike a strict lookup, missing
+/// columns are simply omitted from the result rather than treated as an
+/// error, since a file's numeric columns (e.g. `shape_dist_traveled`) may be
+/// optional.
 ///
 /// # Arguments
 /// * `headers` - The StringRecord containing the header row.
-/// * `col_names` - A slice of strings representing the column names to find.
+/// * `col_names` - A slice of strings representing the column names to look for.
 ///
 /// # Returns
-/// A `Result` containing a `HashMap` mapping column names (lowercase) to their 0-based indices,
-/// or an `Err` if any of the specified columns are not found.
-fn find_column_indices(
-    headers: &StringRecord,
-    col_names: &[&str],
-) -> Result<HashMap<String, usize>, Box<dyn Error>> {
-    let mut indices = HashMap::new();
-    // Create a map for quick lookup of header names (lowercase) and their indices
+/// A `HashMap` mapping column names (lowercase) to their 0-based indices,
+/// containing only the columns that were actually found.
+fn find_present_columns(headers: &StringRecord, col_names: &[String]) -> HashMap<String, usize> {
     let header_map: HashMap<_, _> = headers
         .iter()
         .enumerate()
         .map(|(i, name)| (name.trim().to_lowercase(), i))
         .collect();
 
-    // Find the index for each required column name
-    for &name in col_names {
-        let lower_name = name.to_lowercase(); // Use lowercase for lookup and storage
-        if let Some(&index) = header_map.get(&lower_name) {
-            indices.insert(lower_name, index);
-        } else {
-            // If a required column is missing, return an error
-            return Err(format!("Required column '{}' not found in header.", name).into());
-        }
-    }
-    Ok(indices)
+    col_names
+        .iter()
+        .filter_map(|name| {
+            let lower_name = name.to_lowercase();
+            header_map.get(&lower_name).map(|&idx| (lower_name, idx))
+        })
+        .collect()
+}
+
+/// Options controlling how a single file is processed, threaded through from
+/// the CLI instead of being read from globals so behavior can be tuned per
+/// feed without recompiling.
+struct ProcessOptions<'a> {
+    precision: usize,
+    output_dir: Option<&'a Path>,
+    dry_run: bool,
+    format: format::OutputFormat,
+    report: &'a validation::ValidationReport,
 }
 
-/// Generic function to process a GTFS file (like stops.txt or shapes.txt).
-/// Reads the specified file, fixes coordinate formats in the given lat/lon columns,
-/// and overwrites the original file using a temporary file. Finds columns dynamically.
+/// Generic function to process a GTFS file according to a `FileSpec`.
+/// Reads the specified file, fixes coordinate formats in whichever of its
+/// `numeric_columns` are present, and writes the result either over the
+/// original (via a temporary file) or into `output_dir`. In dry-run mode
+/// nothing is written at all; fields are only compared to see whether they
+/// would change.
 ///
 /// # Arguments
 /// * `gtfs_dir` - Path to the directory containing the GTFS files.
-/// * `filename` - The name of the file to process (e.g., "stops.txt").
-/// * `lat_col_name` - The name of the latitude column to fix.
-/// * `lon_col_name` - The name of the longitude column to fix.
+/// * `spec` - The file to process and the numeric columns it may contain.
+/// * `opts` - Precision, output location, and dry-run behavior for this run.
 ///
 /// # Returns
-/// `Ok(())` on success, or an `Err` containing the error information.
-fn process_gtfs_file(
+/// A `FileOutcome` describing what happened; processing errors are captured
+/// in the outcome rather than propagated, so one failing file never stops
+/// the rest of the feed from being fixed.
+fn process_gtfs_file(gtfs_dir: &Path, spec: &FileSpec, opts: &ProcessOptions) -> FileOutcome {
+    match process_gtfs_file_inner(gtfs_dir, spec, opts) {
+        Ok(outcome) => outcome,
+        Err(e) => FileOutcome::Failed(e.to_string()),
+    }
+}
+
+fn process_gtfs_file_inner(
     gtfs_dir: &Path,
-    filename: &str,
-    lat_col_name: &str,
-    lon_col_name: &str,
-) -> Result<(), Box<dyn Error>> {
-    // Construct full paths for input and temporary output files
+    spec: &FileSpec,
+    opts: &ProcessOptions,
+) -> Result<FileOutcome, Box<dyn Error>> {
+    let filename = &spec.filename;
     let input_path = gtfs_dir.join(filename);
-    let temp_output_filename = format!("{}{}", filename, TEMP_SUFFIX);
-    let temp_output_path = gtfs_dir.join(&temp_output_filename);
-
-    println!("\nStarting processing of '{}'...", input_path.display());
 
     // --- Input File Handling ---
     if !input_path.exists() {
-        // It's not necessarily an error if an optional file like shapes.txt doesn't exist
-        println!(
-            "Info: File '{}' not found in directory '{}'. Skipping processing.",
-            filename,
-            gtfs_dir.display()
-        );
-        return Ok(()); // Return Ok to allow processing of other files
+        // It's not necessarily an error if an optional file doesn't exist
+        return Ok(FileOutcome::Skipped);
     }
     let input_file = File::open(&input_path)?;
     let reader = BufReader::new(input_file);
@@ -117,203 +291,327 @@ fn process_gtfs_file(
         .has_headers(true) // Read the first row as a header
         .from_reader(reader);
 
-    // --- Temporary Output File Handling ---
-    // Defer file creation until header is successfully read and columns found
-    let temp_output_file: File;
-    let mut csv_writer: Writer<BufWriter<File>>; // Declare writer
-
     // --- Header Processing & Column Index Finding ---
     let headers = csv_reader.headers()?.clone(); // Clone to own the data
 
-    // Find the indices of the latitude and longitude columns dynamically
-    let required_columns = [lat_col_name, lon_col_name];
-    let column_indices = match find_column_indices(&headers, &required_columns) {
-        Ok(indices) => indices,
-        Err(e) => {
-            // Specific error for column finding
-            eprintln!(
-                "Error finding columns in '{}': {}. Skipping processing.",
-                input_path.display(),
-                e
+    // Find whichever numeric columns are actually present in this file
+    let column_indices = find_present_columns(&headers, &spec.numeric_columns);
+    if column_indices.is_empty() {
+        if !spec.columns_required {
+            // Every numeric column for this file is optional (e.g.
+            // `shape_dist_traveled` on stop_times.txt); a feed that omits
+            // them entirely is still valid, so there's nothing to fix.
+            return Ok(FileOutcome::Skipped);
+        }
+        for column in &spec.numeric_columns {
+            opts.report.push(
+                filename,
+                0,
+                validation::IssueKind::MissingColumn {
+                    column: column.clone(),
+                },
             );
-            // No temporary file created yet, so no cleanup needed here
-            return Err(e); // Propagate the error
         }
+        return Err(format!(
+            "none of the expected columns [{}] were found in header",
+            spec.numeric_columns.join(", ")
+        )
+        .into());
+    }
+    let fixed_indices: Vec<usize> = column_indices.values().copied().collect();
+    let mut fixed_columns: Vec<String> = column_indices.keys().cloned().collect();
+    fixed_columns.sort();
+    let max_idx = fixed_indices.iter().copied().max().unwrap_or(0);
+    let index_to_column: HashMap<usize, String> = column_indices
+        .iter()
+        .map(|(name, &idx)| (idx, name.clone()))
+        .collect();
+
+    // --- Output File Handling ---
+    // In dry-run mode nothing is written; otherwise write either into
+    // `output_dir` directly or over the original via a temporary file.
+    // Non-CSV formats always go through `output_dir` (enforced by the
+    // caller), since their filename carries a different extension than the
+    // GTFS original.
+    let (temp_output_path, final_output_path) = if opts.dry_run {
+        (None, None)
+    } else if let Some(output_dir) = opts.output_dir {
+        fs::create_dir_all(output_dir)?;
+        let out_name = format::output_filename(filename, opts.format);
+        (None, Some(output_dir.join(out_name)))
+    } else {
+        let temp_path = gtfs_dir.join(format!("{}{}", filename, TEMP_SUFFIX));
+        (Some(temp_path.clone()), Some(input_path.clone()))
+    };
+
+    let mut record_writer: Option<format::RecordWriter> = match (&temp_output_path, &final_output_path) {
+        (Some(temp_path), _) => Some(format::RecordWriter::create(temp_path, opts.format)?),
+        (None, Some(out_path)) => Some(format::RecordWriter::create(out_path, opts.format)?),
+        (None, None) => None,
     };
 
-    // Retrieve the specific indices (unwrap is safe here due to the check in find_column_indices)
-    // Use lowercase for HashMap lookup
-    let lat_col_idx = *column_indices.get(&lat_col_name.to_lowercase()).unwrap();
-    let lon_col_idx = *column_indices.get(&lon_col_name.to_lowercase()).unwrap();
-
-    println!(
-        "Found Latitude column: '{}' (Index {})",
-        headers.get(lat_col_idx).unwrap_or("N/A"), // Get original header name for display
-        lat_col_idx
-    );
-    println!(
-        "Found Longitude column: '{}' (Index {})",
-        headers.get(lon_col_idx).unwrap_or("N/A"), // Get original header name for display
-        lon_col_idx
-    );
-
-    // Now create the temporary file and writer
-    temp_output_file = File::create(&temp_output_path)?;
-    let writer = BufWriter::new(temp_output_file);
-    csv_writer = Writer::from_writer(writer);
-
-    // Write the original header to the temporary output file
-    csv_writer.write_record(&headers)?;
-    println!(
-        "Header written to temporary file '{}'.",
-        temp_output_path.display()
-    );
+    if let Some(writer) = record_writer.as_mut() {
+        writer.write_header(&headers)?;
+    }
 
     // --- Record Processing ---
     let mut processed_count = 0;
+    let mut changed_count = 0;
     let mut record = StringRecord::new(); // Reusable record
 
     // Iterate over each data record in the input file
     while csv_reader.read_record(&mut record)? {
+        let line = record
+            .position()
+            .map(|p| p.line())
+            .unwrap_or(processed_count as u64 + 1);
+
         // Ensure the record has enough fields (robustness against malformed rows)
-        if record.len() <= lat_col_idx || record.len() <= lon_col_idx {
-             eprintln!(
-                "\nWarning: Skipping malformed row {} ({} fields) in '{}'. Expected at least {} fields.",
-                processed_count + 1, // +1 for 1-based row number (approx)
-                record.len(),
+        if record.len() <= max_idx {
+            opts.report.push(
                 filename,
-                std::cmp::max(lat_col_idx, lon_col_idx) + 1
+                line,
+                validation::IssueKind::ShortRow {
+                    found: record.len(),
+                    needed: max_idx + 1,
+                },
             );
             continue; // Skip this row
         }
 
-        let mut output_fields: Vec<String> = Vec::with_capacity(record.len());
-
-        // Process each field, formatting coordinates based on the found indices
-        for (index, field) in record.iter().enumerate() {
-            let processed_field = if index == lat_col_idx || index == lon_col_idx {
-                // If it's the dynamically found lat or lon column, format it
-                format_coordinate(field)
-            } else {
-                // Otherwise, keep the field as is
-                field.to_string()
-            };
-            output_fields.push(processed_field);
+        let output_fields: Vec<String> = record
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                if fixed_indices.contains(&index) {
+                    let column = index_to_column.get(&index).cloned().unwrap_or_default();
+                    let trimmed = field.trim();
+                    // Optional numeric columns (e.g. shape_dist_traveled) are
+                    // routinely left blank on a per-row basis; that's not malformed.
+                    if !trimmed.is_empty() {
+                        match trimmed.parse::<f64>() {
+                            Ok(val) if !validation::is_in_range(&column, val) => {
+                                opts.report.push(
+                                    filename,
+                                    line,
+                                    validation::IssueKind::OutOfRangeLatLon {
+                                        column: column.clone(),
+                                        value: val,
+                                    },
+                                );
+                            }
+                            Err(_) => {
+                                opts.report.push(
+                                    filename,
+                                    line,
+                                    validation::IssueKind::UnparseableCoordinate {
+                                        column: column.clone(),
+                                        value: field.to_string(),
+                                    },
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                    let fixed = format_coordinate(field, opts.precision);
+                    if fixed != field {
+                        changed_count += 1;
+                    }
+                    fixed
+                } else {
+                    field.to_string()
+                }
+            })
+            .collect();
+
+        if let Some(writer) = record_writer.as_mut() {
+            writer.write_record(&headers, &output_fields, &fixed_indices)?;
         }
-
-        // Write the potentially modified record to the temporary output CSV
-        csv_writer.write_record(&output_fields)?;
         processed_count += 1;
-
-        // Optional: Progress indicator
-        if processed_count % 5000 == 0 { // Adjusted frequency
-            print!("\rProcessed {} records for {}...", processed_count, filename);
-            stdout().flush()?; // Ensure the progress message is displayed immediately
-        }
     }
-    println!(
-        "\rProcessed {} records for {}.      ", // Clear progress line
-        processed_count, filename
-    );
 
     // --- Finalisation ---
-    // Ensure all buffered data is written to the temporary file
-    csv_writer.flush()?;
-    println!(
-        "Successfully processed {} records from '{}' to temporary file.",
-        processed_count, filename
-    );
-
-    // --- Replace Original File ---
-    // Rename the temporary file to the original filename, overwriting it.
-    rename(&temp_output_path, &input_path)?;
-    println!(
-        "Successfully replaced '{}' with the processed data.",
-        input_path.display()
-    );
-
-    Ok(())
+    if let Some(writer) = record_writer {
+        writer.finish()?;
+    }
+
+    // --- Replace Original File (in-place mode only) ---
+    if let (Some(temp_path), Some(input_path)) = (&temp_output_path, &final_output_path) {
+        rename(temp_path, input_path)?;
+    }
+
+    Ok(FileOutcome::Processed {
+        processed_count,
+        changed_count,
+        columns: fixed_columns,
+        dry_run: opts.dry_run,
+    })
 }
 
-fn main() {
-    // --- Argument Parsing ---
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <path_to_gtfs_directory>", args[0]);
-        eprintln!("Example: {} /path/to/your/gtfs_feed/", args[0]);
-        std::process::exit(1);
+/// Removes a leftover temporary file for `filename`, if one exists, after a
+/// failed processing attempt.
+fn cleanup_temp_file(gtfs_dir: &Path, filename: &str) {
+    let temp_output_path = gtfs_dir.join(format!("{}{}", filename, TEMP_SUFFIX));
+    if temp_output_path.exists() {
+        if let Err(remove_err) = remove_file(&temp_output_path) {
+            eprintln!(
+                "Additionally, failed to remove temporary file '{}': {}",
+                temp_output_path.display(),
+                remove_err
+            );
+        }
     }
+}
 
-    let gtfs_dir_path = PathBuf::from(&args[1]);
+fn main() {
+    let opt = Opt::parse();
 
     // --- Directory Validation ---
-    if !gtfs_dir_path.is_dir() {
+    if !opt.gtfs_dir.is_dir() {
         eprintln!(
             "Error: Provided path '{}' is not a valid directory.",
-            gtfs_dir_path.display()
+            opt.gtfs_dir.display()
         );
         std::process::exit(1);
     }
 
-    // --- Execute Processing for stops.txt ---
-    let stops_result = process_gtfs_file(
-        &gtfs_dir_path,
-        STOPS_FILENAME,
-        STOP_LAT_COLUMN_NAME,
-        STOP_LON_COLUMN_NAME,
-    );
-
-    if let Err(e) = stops_result {
-        eprintln!(
-            "\nAn error occurred during processing of '{}': {}",
-            STOPS_FILENAME, e
-        );
-        // Attempt to clean up temporary file if it exists
-        let temp_output_path = gtfs_dir_path.join(format!("{}{}", STOPS_FILENAME, TEMP_SUFFIX));
-        if temp_output_path.exists() {
-            if let Err(remove_err) = remove_file(&temp_output_path) {
-                eprintln!(
-                    "Additionally, failed to remove temporary file '{}': {}",
-                    temp_output_path.display(),
-                    remove_err
+    // --- Clip mode: extract a geographic sub-feed instead of fixing in place ---
+    if let Some(bbox) = opt.bbox {
+        let Some(output_dir) = opt.output_dir.as_deref() else {
+            eprintln!("Error: --bbox requires --output-dir to write the clipped feed to.");
+            std::process::exit(1);
+        };
+        match clip::clip_feed(&opt.gtfs_dir, output_dir, bbox, opt.shape_clip_mode) {
+            Ok(summary) => {
+                println!(
+                    "Clipped stops.txt: kept {}/{} stop(s).",
+                    summary.stops_kept, summary.stops_total
+                );
+                println!(
+                    "Clipped shapes.txt: kept {}/{} shape point(s).",
+                    summary.shape_points_kept, summary.shape_points_total
+                );
+                println!(
+                    "Copied {} other feed file(s) through unfiltered.",
+                    summary.passthrough_files
                 );
-            } else {
-                eprintln!("Removed temporary file '{}'.", temp_output_path.display());
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error clipping feed: {}", e);
+                std::process::exit(1);
             }
         }
-        // Decide whether to exit or continue with shapes.txt
-        // For now, let's exit on error for stops.txt as it's often crucial.
+    }
+
+    if opt.format != format::OutputFormat::Csv && opt.output_dir.is_none() {
+        eprintln!("Error: --format ndjson/json requires --output-dir, since the written files use a different extension than the GTFS originals.");
         std::process::exit(1);
     }
 
-    // --- Execute Processing for shapes.txt ---
-    let shapes_result = process_gtfs_file(
-        &gtfs_dir_path,
-        SHAPES_FILENAME,
-        SHAPE_LAT_COLUMN_NAME,
-        SHAPE_LON_COLUMN_NAME,
-    );
+    let mut files = known_files();
+    files.extend(opt.extra_file.iter().cloned());
 
-    if let Err(e) = shapes_result {
-        eprintln!(
-            "\nAn error occurred during processing of '{}': {}",
-            SHAPES_FILENAME, e
-        );
-        // Attempt to clean up temporary file if it exists
-        let temp_output_path = gtfs_dir_path.join(format!("{}{}", SHAPES_FILENAME, TEMP_SUFFIX));
-        if temp_output_path.exists() {
-            if let Err(remove_err) = remove_file(&temp_output_path) {
-                eprintln!(
-                    "Additionally, failed to remove temporary file '{}': {}",
-                    temp_output_path.display(),
-                    remove_err
-                );
-            } else {
-                eprintln!("Removed temporary file '{}'.", temp_output_path.display());
+    let validation_report = validation::ValidationReport::new();
+    let process_opts = ProcessOptions {
+        precision: opt.precision,
+        output_dir: opt.output_dir.as_deref(),
+        dry_run: opt.dry_run,
+        format: opt.format,
+        report: &validation_report,
+    };
+
+    // --- Process every known file concurrently, one thread per file ---
+    // Large feeds can carry many multi-million-row files; fixing them on a
+    // scoped thread pool means the whole feed finishes in roughly the time
+    // of its slowest file rather than the sum of all of them.
+    let reports: Vec<FileReport> = thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .iter()
+            .map(|spec| {
+                let gtfs_dir = &opt.gtfs_dir;
+                let process_opts = &process_opts;
+                scope.spawn(move || {
+                    let outcome = process_gtfs_file(gtfs_dir, spec, process_opts);
+                    if matches!(outcome, FileOutcome::Failed(_)) && !process_opts.dry_run {
+                        cleanup_temp_file(gtfs_dir, &spec.filename);
+                    }
+                    FileReport {
+                        filename: spec.filename.clone(),
+                        outcome,
+                    }
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    // --- Summary ---
+    if opt.dry_run {
+        println!("\nGTFS dry-run summary (no fixed files were written):");
+    } else {
+        println!("\nGTFS fix summary:");
+    }
+    let mut had_failure = false;
+    for report in &reports {
+        println!("  {}", report);
+        if matches!(report.outcome, FileOutcome::Failed(_)) {
+            had_failure = true;
+        }
+    }
+
+    // --- Optional dedupe pass over stops.txt ---
+    if opt.dedupe_stops && !opt.dry_run {
+        let stops_dir = opt.output_dir.as_deref().unwrap_or(&opt.gtfs_dir);
+        let stops_path = stops_dir.join("stops.txt");
+        let dedupe_opts = dedupe::DedupeOptions {
+            precision: opt.dedupe_precision,
+            threshold_meters: opt.dedupe_threshold_meters,
+            merge: opt.merge,
+        };
+        match dedupe::dedupe_stops(&stops_path, &dedupe_opts) {
+            Ok(dedupe::DedupeOutcome::NotPresent) => {
+                println!("\nstops.txt: not present, skipping dedupe.");
+            }
+            Ok(dedupe::DedupeOutcome::Report {
+                duplicate_groups,
+                merged_stops,
+            }) => {
+                if opt.merge && merged_stops == 0 {
+                    println!("\nstops.txt: no duplicate stops found; nothing merged");
+                } else if opt.merge {
+                    println!(
+                        "\nstops.txt: merged {} duplicate stop(s) across {} group(s); mapping written to stops_merged.csv",
+                        merged_stops, duplicate_groups
+                    );
+                } else {
+                    println!(
+                        "\nstops.txt: found {} duplicate stop(s) across {} group(s) (use --merge to rewrite)",
+                        merged_stops, duplicate_groups
+                    );
+                }
             }
+            Err(e) => {
+                eprintln!("\nstops.txt: dedupe FAILED - {}", e);
+                had_failure = true;
+            }
+        }
+    }
+
+    // --- Validation report ---
+    validation_report.print_summary();
+    if let Some(report_path) = &opt.report {
+        if let Err(e) = validation_report.write_csv(report_path) {
+            eprintln!("\nFailed to write validation report to '{}': {}", report_path.display(), e);
+            had_failure = true;
+        } else {
+            println!("\nValidation report written to '{}'.", report_path.display());
         }
-        std::process::exit(1); // Exit on error for shapes.txt as well
     }
 
+    if had_failure {
+        std::process::exit(1);
+    }
     println!("\nProcessing complete for all files.");
 }